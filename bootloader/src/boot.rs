@@ -0,0 +1,94 @@
+//! Boot protocol handed to the loaded image. A `BootInfo` structure lives at a
+//! fixed low-memory address and carries the TFTP-fetched kernel command line
+//! and an optional initial ramdisk, replacing the bare
+//! `entry(_bootloader_size: usize)` contract.
+
+use core::convert::TryInto;
+
+/// Well-known TFTP path for the kernel command line
+pub const CMDLINE_PATH: &str = "caramel.cmdline";
+
+/// Well-known TFTP path for the initial ramdisk
+pub const INITRD_PATH: &str = "caramel.initrd";
+
+/// Fixed low-memory address where the `BootInfo` is published so the loaded
+/// image's entry point can find it.
+pub const BOOT_INFO_ADDR: usize = 0x7000;
+
+/// Information passed from the bootloader to the loaded image. Pointers are
+/// physical addresses; a zero length means the corresponding item is absent.
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub struct BootInfo {
+    /// Pointer to the command-line string
+    pub cmdline_ptr: u64,
+
+    /// Length of the command-line string in bytes
+    pub cmdline_len: u64,
+
+    /// Pointer to the initrd image
+    pub initrd_ptr: u64,
+
+    /// Length of the initrd image in bytes
+    pub initrd_len: u64,
+}
+
+impl BootInfo {
+    /// Publish this `BootInfo` at the fixed low-memory address for the loaded
+    /// image to pick up, returning that address.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure `BOOT_INFO_ADDR` is owned by the bootloader and
+    /// not overlapping live allocations.
+    pub unsafe fn install(&self) -> usize {
+        core::ptr::write(BOOT_INFO_ADDR as *mut BootInfo, *self);
+        BOOT_INFO_ADDR
+    }
+}
+
+/// A parsed kernel command line, exposing its whitespace-separated
+/// `key=value` pairs.
+pub struct CmdLine<'a> {
+    /// Raw command-line text
+    raw: &'a str,
+}
+
+impl<'a> CmdLine<'a> {
+    /// Wrap raw command-line bytes, returning `None` if they are not valid
+    /// UTF-8
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        Some(CmdLine { raw: core::str::from_utf8(bytes).ok()? })
+    }
+
+    /// Look up the value of `key`, returning `None` if it is absent. A bare
+    /// flag (no `=`) yields an empty string.
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        for token in self.raw.split_whitespace() {
+            match token.find('=') {
+                Some(eq) if &token[..eq] == key => return Some(&token[eq + 1..]),
+                None if token == key => return Some(""),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Read back the `BootInfo` published at `BOOT_INFO_ADDR`.
+///
+/// # Safety
+///
+/// The caller must ensure a valid `BootInfo` was installed there.
+pub unsafe fn installed() -> BootInfo {
+    core::ptr::read(BOOT_INFO_ADDR as *const BootInfo)
+}
+
+/// Reconstruct a byte slice from a `(ptr, len)` pair stored in a `BootInfo`.
+///
+/// # Safety
+///
+/// `ptr`/`len` must describe a valid, live region of memory.
+pub unsafe fn slice_from(ptr: u64, len: u64) -> &'static [u8] {
+    core::slice::from_raw_parts(ptr as *const u8, len.try_into().unwrap())
+}
@@ -0,0 +1,248 @@
+//! A flat GDT and a 256-entry IDT with handlers for the architectural
+//! exceptions (vectors 0..32). A fault dumps the saved register frame and the
+//! faulting address over the serial port and halts, turning a silent
+//! triple-fault reset into a readable report.
+
+use serial::print;
+
+/// Selector for the 64-bit code segment in our flat GDT (entry index 3)
+const KERNEL_CS: u16 = 3 << 3;
+
+/// Number of entries in the IDT
+const IDT_ENTRIES: usize = 256;
+
+/// Pointer passed to `lgdt`/`lidt`
+#[repr(C, packed)]
+struct TablePointer {
+    /// Size of the table in bytes, minus one
+    limit: u16,
+
+    /// Linear base address of the table
+    base: u64,
+}
+
+/// Our flat global descriptor table: null, 32-bit code, data, 64-bit code and
+/// 64-bit data descriptors.
+static GDT: [u64; 5] = [
+    0x0000000000000000, // null
+    0x00cf9a000000ffff, // 32-bit code, base 0, limit 4 GiB
+    0x00cf92000000ffff, // data, base 0, limit 4 GiB
+    0x00209a0000000000, // 64-bit code
+    0x0000920000000000, // 64-bit data
+];
+
+/// A 64-bit IDT gate descriptor. The 64-bit handler offset is split across
+/// three fields.
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+struct IdtEntry {
+    offset_low: u16,
+    selector: u16,
+    ist: u8,
+    type_attr: u8,
+    offset_mid: u16,
+    offset_high: u32,
+    zero: u32,
+}
+
+impl IdtEntry {
+    /// An empty, not-present gate
+    const fn missing() -> Self {
+        IdtEntry {
+            offset_low: 0,
+            selector: 0,
+            ist: 0,
+            type_attr: 0,
+            offset_mid: 0,
+            offset_high: 0,
+            zero: 0,
+        }
+    }
+
+    /// Build a present, DPL 0, 64-bit interrupt gate pointing at `handler`
+    fn interrupt_gate(handler: usize) -> Self {
+        IdtEntry {
+            offset_low: handler as u16,
+            selector: KERNEL_CS,
+            ist: 0,
+            // present (0x80) | interrupt gate (0x0e)
+            type_attr: 0x8e,
+            offset_mid: (handler >> 16) as u16,
+            offset_high: (handler >> 32) as u32,
+            zero: 0,
+        }
+    }
+}
+
+/// The interrupt descriptor table
+static mut IDT: [IdtEntry; IDT_ENTRIES] = [IdtEntry::missing(); IDT_ENTRIES];
+
+/// The register frame built by the common stub, as seen by `interrupt_dispatch`.
+/// Field order matches the `push` sequence in `common_interrupt_stub` followed
+/// by the vector/error code and the CPU-pushed exception frame.
+#[repr(C)]
+struct InterruptFrame {
+    r15: u64, r14: u64, r13: u64, r12: u64,
+    r11: u64, r10: u64, r9: u64,  r8: u64,
+    rbp: u64, rdi: u64, rsi: u64, rdx: u64,
+    rcx: u64, rbx: u64, rax: u64,
+
+    /// Interrupt vector number pushed by the per-vector stub
+    vector: u64,
+
+    /// Error code (pushed by the CPU for some vectors, 0 otherwise)
+    error_code: u64,
+
+    /// CPU-pushed exception frame
+    rip: u64,
+    cs: u64,
+    rflags: u64,
+    rsp: u64,
+    ss: u64,
+}
+
+/// Common handler invoked by every exception stub. Dumps the faulting context
+/// and halts.
+#[no_mangle]
+extern "C" fn interrupt_dispatch(frame: &InterruptFrame) -> ! {
+    // Read CR2 (the faulting linear address for page faults)
+    let cr2: u64;
+    unsafe {
+        llvm_asm!("mov $0, cr2" : "=r"(cr2) ::: "intel");
+    }
+
+    print!("\n*** EXCEPTION {:#x} (error code {:#x}) ***\n",
+        frame.vector, frame.error_code);
+    print!("rip={:#018x} cs={:#x} rflags={:#018x}\n",
+        frame.rip, frame.cs, frame.rflags);
+    print!("rsp={:#018x} ss={:#x} cr2={:#018x}\n",
+        frame.rsp, frame.ss, cr2);
+    print!("rax={:#018x} rbx={:#018x} rcx={:#018x} rdx={:#018x}\n",
+        frame.rax, frame.rbx, frame.rcx, frame.rdx);
+    print!("rsi={:#018x} rdi={:#018x} rbp={:#018x}\n",
+        frame.rsi, frame.rdi, frame.rbp);
+    print!("r8 ={:#018x} r9 ={:#018x} r10={:#018x} r11={:#018x}\n",
+        frame.r8, frame.r9, frame.r10, frame.r11);
+    print!("r12={:#018x} r13={:#018x} r14={:#018x} r15={:#018x}\n",
+        frame.r12, frame.r13, frame.r14, frame.r15);
+
+    cpu::halt();
+}
+
+/// The shared tail of every exception stub. The per-vector stub has already
+/// pushed the error code and the vector number; here we save the general
+/// purpose registers, hand a pointer to the frame to `interrupt_dispatch` and
+/// (since it never returns) halt.
+#[naked]
+unsafe extern "C" fn common_interrupt_stub() {
+    llvm_asm!(r#"
+        push rax
+        push rbx
+        push rcx
+        push rdx
+        push rsi
+        push rdi
+        push rbp
+        push r8
+        push r9
+        push r10
+        push r11
+        push r12
+        push r13
+        push r14
+        push r15
+
+        mov rdi, rsp
+        call interrupt_dispatch
+    "# :::: "volatile", "intel");
+}
+
+/// Generate a per-vector stub. Vectors that do not push a hardware error code
+/// push a zero so every frame has the same layout.
+macro_rules! exception_stub {
+    ($name:ident, $vec:expr, no_err) => {
+        #[naked]
+        unsafe extern "C" fn $name() {
+            llvm_asm!(concat!(
+                "push 0\n",
+                "push ", stringify!($vec), "\n",
+                "jmp ${0:c}")
+                :: "i"(common_interrupt_stub as usize) :: "volatile", "intel");
+        }
+    };
+    ($name:ident, $vec:expr, err) => {
+        #[naked]
+        unsafe extern "C" fn $name() {
+            llvm_asm!(concat!(
+                "push ", stringify!($vec), "\n",
+                "jmp ${0:c}")
+                :: "i"(common_interrupt_stub as usize) :: "volatile", "intel");
+        }
+    };
+}
+
+// The architectural exceptions. Vectors 8, 10-14, 17, 21, 29 and 30 push an
+// error code; the rest do not.
+exception_stub!(isr0,  0,  no_err);
+exception_stub!(isr1,  1,  no_err);
+exception_stub!(isr2,  2,  no_err);
+exception_stub!(isr3,  3,  no_err);
+exception_stub!(isr4,  4,  no_err);
+exception_stub!(isr5,  5,  no_err);
+exception_stub!(isr6,  6,  no_err);
+exception_stub!(isr7,  7,  no_err);
+exception_stub!(isr8,  8,  err);
+exception_stub!(isr9,  9,  no_err);
+exception_stub!(isr10, 10, err);
+exception_stub!(isr11, 11, err);
+exception_stub!(isr12, 12, err);
+exception_stub!(isr13, 13, err);
+exception_stub!(isr14, 14, err);
+exception_stub!(isr15, 15, no_err);
+exception_stub!(isr16, 16, no_err);
+exception_stub!(isr17, 17, err);
+exception_stub!(isr18, 18, no_err);
+exception_stub!(isr19, 19, no_err);
+exception_stub!(isr20, 20, no_err);
+exception_stub!(isr21, 21, err);
+exception_stub!(isr22, 22, no_err);
+exception_stub!(isr23, 23, no_err);
+exception_stub!(isr24, 24, no_err);
+exception_stub!(isr25, 25, no_err);
+exception_stub!(isr26, 26, no_err);
+exception_stub!(isr27, 27, no_err);
+exception_stub!(isr28, 28, no_err);
+exception_stub!(isr29, 29, err);
+exception_stub!(isr30, 30, err);
+exception_stub!(isr31, 31, no_err);
+
+/// Install the flat GDT and an IDT with handlers for the architectural
+/// exceptions, then load both with `lgdt`/`lidt`.
+pub fn init() {
+    // Load the flat GDT
+    let gdtr = TablePointer {
+        limit: (core::mem::size_of_val(&GDT) - 1) as u16,
+        base: GDT.as_ptr() as u64,
+    };
+    unsafe { cpu::lgdt(&gdtr as *const _ as usize); }
+
+    // The architectural exception handlers, indexed by vector
+    let handlers: [unsafe extern "C" fn(); 32] = [
+        isr0,  isr1,  isr2,  isr3,  isr4,  isr5,  isr6,  isr7,
+        isr8,  isr9,  isr10, isr11, isr12, isr13, isr14, isr15,
+        isr16, isr17, isr18, isr19, isr20, isr21, isr22, isr23,
+        isr24, isr25, isr26, isr27, isr28, isr29, isr30, isr31,
+    ];
+
+    unsafe {
+        for (vector, &handler) in handlers.iter().enumerate() {
+            IDT[vector] = IdtEntry::interrupt_gate(handler as usize);
+        }
+
+        let idtr = TablePointer {
+            limit: (core::mem::size_of_val(&IDT) - 1) as u16,
+            base: IDT.as_ptr() as u64,
+        };
+        cpu::lidt(&idtr as *const _ as usize);
+    }
+}
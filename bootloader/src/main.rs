@@ -3,10 +3,13 @@
 // https://doc.rust-lang.org/reference/attributes.html
 #![no_std]
 #![no_main]
-#![feature(rustc_private, llvm_asm, panic_info_message, alloc_error_handler)]
+#![feature(rustc_private, llvm_asm, panic_info_message, alloc_error_handler,
+    naked_functions)]
 
 extern crate alloc;
 
+use alloc::vec;
+
 // This declaration will look for a file name `core_reqs.rs` or 
 // `core_reqs/mod.rs` and
 // will insert its contents inside a module named `core_reqs` under this scope
@@ -15,14 +18,46 @@ mod realmode;
 mod mm;
 mod panic;
 mod pxe;
+mod interrupts;
+mod boot;
 
 // Used to not change the function name by compiler mangling
 #[no_mangle]
 extern fn entry(_bootloader_size: usize) -> ! {
     serial::init();
+    interrupts::init();
     mm::init();
 
-    pxe::download();
+    // Download the next stage into a buffer for the loader to parse
+    let mut stage = vec![0u8; 32 * 1024 * 1024];
+    let size = pxe::download("caramel.kern", &mut stage)
+        .expect("Failed to download the next stage over PXE");
+    serial::print!("Downloaded {} bytes\n", size);
+
+    // Fetch the command line and the optional initrd over TFTP. Both are
+    // optional; a missing file just leaves the corresponding field empty.
+    let mut boot_info = boot::BootInfo::default();
+
+    let mut cmdline = vec![0u8; 4096];
+    if let Some(len) = pxe::download(boot::CMDLINE_PATH, &mut cmdline) {
+        boot_info.cmdline_ptr = cmdline.as_ptr() as u64;
+        boot_info.cmdline_len = len as u64;
+
+        if let Some(cmdline) = boot::CmdLine::new(&cmdline[..len]) {
+            if let Some(root) = cmdline.get("root") {
+                serial::print!("Command line root={}\n", root);
+            }
+        }
+    }
+
+    let mut initrd = vec![0u8; 64 * 1024 * 1024];
+    if let Some(len) = pxe::download(boot::INITRD_PATH, &mut initrd) {
+        boot_info.initrd_ptr = initrd.as_ptr() as u64;
+        boot_info.initrd_len = len as u64;
+    }
+
+    // Publish the boot info for the loaded image to consume
+    unsafe { boot_info.install(); }
 
     cpu::halt();
 }
@@ -3,122 +3,428 @@ use rangeset::{Range, RangeSet};
 use lockcell::LockCell;
 use crate::realmode::{RegisterState, invoke_realmode};
 
-/// Physical memory which is available for use. As reported by E820 with
-/// the 1 MiB of memory removed
-static PMEM_FREE: LockCell<Option<RangeSet>> = LockCell::new(None);
+/// UEFI allocation type `AllocateAnyPages`
+const ALLOCATE_ANY_PAGES: u32 = 0;
 
-/// Global allocator for the bootloader. This just uses physical memory as
-/// a backing and does not handle any fancy things like fragmentation. Use
-/// this carefully.
+/// UEFI memory type `EfiLoaderData`, used for our page allocations
+const EFI_LOADER_DATA: u32 = 2;
+
+/// Size of a UEFI memory page
+const EFI_PAGE_SIZE: usize = 4096;
+
+/// Bytes reserved immediately before each BIOS allocation to record the real
+/// reserved base and full length (including alignment padding), so `dealloc`
+/// can give back exactly what `alloc` took.
+const ALLOC_HEADER: u64 = 16;
+
+/// Round `val` up to the next multiple of `align` (a power of two)
+fn align_up(val: u64, align: u64) -> u64 {
+    (val + align - 1) & !(align - 1)
+}
+
+/// The UEFI `AllocatePages`/`FreePages` boot service function pointers we use
+/// while boot services are still alive. Stored directly instead of modeling the
+/// whole boot services table.
+///
+/// Intentionally-unused scaffolding: the `Uefi` backend below is not wired to a
+/// caller yet. The current EFI boot path ([`init_uefi`]) runs after
+/// ExitBootServices and seeds the `Bios` `RangeSet` backend directly from
+/// `GetMemoryMap`; installing this firmware-backed variant requires a
+/// pre-ExitBootServices entry point that passes the boot-services pointers,
+/// which does not exist in this tree.
+#[derive(Clone, Copy)]
+struct UefiAlloc {
+    allocate_pages: unsafe extern "win64" fn(u32, u32, usize, *mut u64) -> usize,
+    free_pages: unsafe extern "win64" fn(u64, usize) -> usize,
+}
+
+/// The active allocation backend. The same binary can run under legacy BIOS
+/// (where free RAM comes from the E820-derived `RangeSet`) or UEFI (where
+/// firmware `AllocatePages`/`FreePages` serve allocations until boot services
+/// are exited and the map is converted to a `RangeSet`).
+enum Allocator {
+    /// No memory manager yet; allocations fail
+    Uninitialized,
+
+    /// Physical memory tracked in a `RangeSet` derived from E820 (or the UEFI
+    /// map after exiting boot services). We reuse `RangeSet` rather than thread
+    /// a free list through the freed pages themselves: it already provides the
+    /// first-fit behavior we want — `allocate` scans for the first range large
+    /// enough for the aligned request and splits the remainder back in, while
+    /// `insert` reinserts freed spans in address order and coalesces adjacent
+    /// neighbors — but it stores those ranges in its own fixed array, so the
+    /// free blocks are *not* intrusively linked through the memory they track.
+    Bios(RangeSet),
+
+    /// UEFI boot-services pages, alive only until `exit_boot_services`
+    Uefi(UefiAlloc),
+}
+
+/// Global allocator for the bootloader, dispatching to whichever backend is
+/// currently active.
 #[global_allocator]
-static GLOBAL_ALLOCATOR: GlobalAllocator = GlobalAllocator;
+static GLOBAL_ALLOCATOR: GlobalAllocator = GlobalAllocator {
+    inner: LockCell::new(Allocator::Uninitialized),
+};
 
-/// Empty structure that we can implement `GlobalAlloc` for such that we can
-/// use the `#[global_allocator]`
-struct GlobalAllocator;
+/// Wrapper owning the active [`Allocator`] behind a `LockCell` so we can
+/// implement `GlobalAlloc` on a `'static`.
+struct GlobalAllocator {
+    inner: LockCell<Allocator>,
+}
+
+impl GlobalAllocator {
+    /// Install the BIOS/E820-backed `RangeSet` backend
+    fn init_bios(&self, free_memory: RangeSet) {
+        let mut alloc = self.inner.lock();
+        assert!(matches!(*alloc, Allocator::Uninitialized),
+            "Attempted to re-initialize the memory manager");
+        *alloc = Allocator::Bios(free_memory);
+    }
+
+    /// Install the UEFI boot-services backend
+    #[allow(dead_code)]
+    fn init_uefi(&self, uefi: UefiAlloc) {
+        let mut alloc = self.inner.lock();
+        assert!(matches!(*alloc, Allocator::Uninitialized),
+            "Attempted to re-initialize the memory manager");
+        *alloc = Allocator::Uefi(uefi);
+    }
+
+    /// Transition off UEFI boot services, replacing the firmware-backed backend
+    /// with a `RangeSet` seeded from the UEFI memory map.
+    #[allow(dead_code)]
+    fn exit_boot_services(&self, free_memory: RangeSet) {
+        let mut alloc = self.inner.lock();
+        *alloc = Allocator::Bios(free_memory);
+    }
+}
 
 unsafe impl GlobalAlloc for GlobalAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        // Get access to physical memory
-        let pmem = PMEM_FREE.lock();
+        let mut alloc = self.inner.lock();
+        match &mut *alloc {
+            Allocator::Uninitialized => core::ptr::null_mut(),
+            Allocator::Bios(free_memory) => {
+                // Reserve enough for the header, the worst-case alignment slack
+                // and the request itself, all with alignment 1 so the allocator
+                // hands back the true base of the reserved span rather than an
+                // aligned pointer that hides the skipped padding.
+                let align = layout.align() as u64;
+                let total = layout.size() as u64 + align + ALLOC_HEADER;
+                let base = match free_memory.allocate(total, 1) {
+                    Some(base) => base as u64,
+                    None => return core::ptr::null_mut(),
+                };
+
+                // Carve out an aligned user pointer with room for the header
+                let user = align_up(base + ALLOC_HEADER, align);
+
+                // Record the real base and full length just before the pointer.
+                // `user` is only aligned to `layout.align()`, which can be less
+                // than 8 (eg. a `Vec<u8>`), so the header itself is not
+                // guaranteed to be `u64`-aligned and must go through the
+                // unaligned accessors.
+                let meta = (user - ALLOC_HEADER) as *mut u64;
+                meta.write_unaligned(base);
+                meta.add(1).write_unaligned(total);
 
-        pmem.and_then(|mut x| {
-            x.allocate(layout.size() as u64, layout.align() as u64)
-        }).unwrap_or(0) as *mut u8
+                user as *mut u8
+            }
+            Allocator::Uefi(uefi) => {
+                // UEFI only allocates whole pages; round the request up
+                let pages = (layout.size() + EFI_PAGE_SIZE - 1) / EFI_PAGE_SIZE;
+                let mut phys = 0u64;
+                let status = (uefi.allocate_pages)(
+                    ALLOCATE_ANY_PAGES, EFI_LOADER_DATA, pages, &mut phys);
+                if status != 0 { core::ptr::null_mut() } else { phys as *mut u8 }
+            }
+        }
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         // We have nothing to free for a zero-size-type
-        if layout.size() <= 0 { return; }
+        if layout.size() == 0 { return; }
 
-        // Get access to physical memory
-        let pmem = PMEM_FREE.lock();
-        pmem.and_then(|mut x| {
-            let end = (ptr as u64).checked_add(layout.size() as u64 - 1)?;
-            x.insert(Range { start: ptr as u64, end: end});
-            Some(())
-        }).expect("Cannot free memory without initialized MM");
+        let mut alloc = self.inner.lock();
+        match &mut *alloc {
+            Allocator::Uninitialized => {
+                panic!("Cannot free memory without initialized MM");
+            }
+            Allocator::Bios(free_memory) => {
+                // Recover the real base and full reserved length recorded by
+                // `alloc` and give back exactly that span, padding included.
+                // The header may not be `u64`-aligned (see `alloc`), hence the
+                // unaligned read.
+                let meta = (ptr as u64 - ALLOC_HEADER) as *const u64;
+                let base = meta.read_unaligned();
+                let total = meta.add(1).read_unaligned();
+                let end = base
+                    .checked_add(total - 1)
+                    .expect("Overflow computing freed range");
+                free_memory.insert(Range { start: base, end });
+            }
+            Allocator::Uefi(uefi) => {
+                let pages = (layout.size() + EFI_PAGE_SIZE - 1) / EFI_PAGE_SIZE;
+                (uefi.free_pages)(ptr as u64, pages);
+            }
+        }
     }
 }
 
+/// The `Bios`/`Uefi` backends above already supply what a "real" allocator
+/// needs — `RangeSet` gives first-fit scanning, remainder-splitting and
+/// coalesce-on-free, and UEFI's own page allocator does the equivalent. This
+/// handler only covers the remaining gap: reporting and halting when either
+/// backend is exhausted, instead of letting `alloc` hand back a null pointer.
 #[alloc_error_handler]
-fn alloc_error(_layout: Layout) -> ! {
-    panic!("Out of memory");
+fn alloc_error(layout: Layout) -> ! {
+    serial::print!("Out of memory: failed to allocate {} bytes (align {})\n",
+        layout.size(), layout.align());
+    cpu::halt();
 }
 
-/// Initialize the physical memory manager. Here we get the memory map from
-/// the BIOS via E820 and put i into a `RangeSet` for tracking and allocation.
-/// We also subtract off the first 1 MiB of memory to prevent BIOS data
-/// structures from being overwritten.
-pub fn init() {
-    // Create a `RangeSet` to hold the memory that is marked free by the
-    // BIOS
-    let mut pmem = PMEM_FREE.lock();
+/// Type-1 usable RAM, as reported by E820
+const E820_USABLE: u32 = 1;
+
+/// Type-3 ACPI reclaimable memory: non-usable, but the least restrictive such
+/// type since the kernel returns it to the allocator after parsing ACPI tables
+const E820_ACPI_RECLAIMABLE: u32 = 3;
+
+/// The complete typed memory map retained for kernel handoff, populated during
+/// the same sweep that feeds free RAM into the allocator. Unlike the
+/// allocator's `RangeSet`, this keeps reserved, ACPI and bad-memory regions.
+static MEMORY_MAP: LockCell<Option<MemoryMap>> = LockCell::new(None);
+
+/// A memory region's type, mapped from the raw E820 type number. ACPI
+/// reclaimable is kept distinct so the kernel can return it to the allocator
+/// once it has finished parsing the ACPI tables.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum E820Type {
+    Usable,
+    Reserved,
+    AcpiReclaimable,
+    AcpiNvs,
+    BadMemory,
+    Other(u32),
+}
+
+impl E820Type {
+    /// Map a raw E820 type number onto an `E820Type`
+    fn from_raw(typ: u32) -> Self {
+        match typ {
+            1 => E820Type::Usable,
+            2 => E820Type::Reserved,
+            3 => E820Type::AcpiReclaimable,
+            4 => E820Type::AcpiNvs,
+            5 => E820Type::BadMemory,
+            other => E820Type::Other(other),
+        }
+    }
+}
+
+/// The full typed memory map. Entries are sorted by address and coalesced with
+/// same-typed contiguous neighbors.
+pub struct MemoryMap {
+    entries: [(Range, E820Type); MAX_E820_ENTRIES * 2],
+    len: usize,
+}
+
+impl MemoryMap {
+    /// An empty map
+    const fn new() -> Self {
+        MemoryMap {
+            entries: [(Range { start: 0, end: 0 }, E820Type::Reserved);
+                MAX_E820_ENTRIES * 2],
+            len: 0,
+        }
+    }
+
+    /// Append `range` with type `typ`, coalescing with the previous entry when
+    /// it has the same type and is immediately contiguous.
+    fn push(&mut self, range: Range, typ: E820Type) {
+        if let Some((last_range, last_typ)) = self.entries[..self.len].last_mut() {
+            if *last_typ == typ && last_range.end.wrapping_add(1) == range.start {
+                last_range.end = range.end;
+                return;
+            }
+        }
+
+        assert!(self.len < self.entries.len(), "Typed memory map overflow");
+        self.entries[self.len] = (range, typ);
+        self.len += 1;
+    }
+}
+
+/// Invoke `func` for each `(Range, E820Type)` entry of the retained typed
+/// memory map, in address order. Panics if the memory manager is uninitialized.
+pub fn memory_map<F: FnMut(Range, E820Type)>(mut func: F) {
+    let map = MEMORY_MAP.lock();
+    let map = map.as_ref().expect("Memory map not initialized");
+    for &(range, typ) in &map.entries[..map.len] {
+        func(range, typ);
+    }
+}
+
+/// Maximum number of E820 entries we are willing to collect. BIOS maps are
+/// small; this is sized generously so overlapping/nested reports still fit.
+const MAX_E820_ENTRIES: usize = 256;
+
+/// Raw E820 entry, to be filled in by the BIOS
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+struct E820Entry {
+    base: u64,
+    size: u64,
+    typ: u32,
+}
 
-    // Make sure we've never initialized the MM before
-    assert!(pmem.is_none(),
-        "Attempted to re-initialize the memory manager");
+/// A change point used by the sanitizer: the address of a region boundary,
+/// whether it opens or closes that region, and which entry it belongs to.
+#[derive(Clone, Copy, Default)]
+struct ChangePoint {
+    /// Boundary address. Addresses are held as `u128` so `end + 1` never wraps
+    /// at the top of the address space.
+    addr: u128,
 
+    /// `true` for a region start, `false` for a region end (exclusive)
+    is_start: bool,
+
+    /// Index of the owning entry in the collected map
+    idx: usize,
+}
+
+/// Collect the raw E820 map from the BIOS into `entries`, dropping zero-size
+/// entries, and return the number of entries stored.
+fn read_e820(entries: &mut [E820Entry]) -> usize {
+    let mut regs = RegisterState::default();
+
+    // Set the continuation code to 0 for the first E820 call
+    regs.ebx = 0;
+    let mut count = 0;
+    loop {
+        // Create a zeroed out E820 entry
+        let mut entry = E820Entry::default();
+
+        // Set up the args for E820, we use the previous continuation code
+        regs.eax = 0xe820;
+        regs.edi = &mut entry as *mut E820Entry as u32;
+        regs.ecx = core::mem::size_of_val(&entry) as u32;
+        regs.edx = u32::from_be_bytes(*b"SMAP");
+
+        // Invoke the BIOS for the E820 memory map
+        unsafe { invoke_realmode(0x15, &mut regs); }
+
+        // Check the CF for an error
+        if (regs.efl & 1) != 0 {
+            panic!("Error reported by BIOS on E820");
+        }
+
+        // Drop zero-size entries; store the rest if there's room
+        if entry.size > 0 {
+            assert!(count < entries.len(), "E820 map larger than expected");
+            entries[count] = entry;
+            count += 1;
+        }
+
+        if regs.ebx == 0 {
+            // Last entry
+            break;
+        }
+    }
+
+    count
+}
+
+/// The priority of an E820 type for the sanitizer: higher wins. Usable RAM is
+/// the lowest priority so any non-usable type overlapping it takes precedence.
+/// ACPI reclaimable is the least restrictive non-usable type — it will later be
+/// handed back to the allocator — so a truly reserved/NVS/bad region overlapping
+/// it must win, regardless of the raw type numbers.
+fn type_priority(typ: u32) -> u32 {
+    match typ {
+        E820_USABLE => 0,
+        E820_ACPI_RECLAIMABLE => 1,
+        _ => 2,
+    }
+}
+
+/// Initialize the physical memory manager. We get the memory map from the BIOS
+/// via E820, run it through a change-point sanitizer modeled on Linux's
+/// `sanitize_e820_map` so overlapping and nested regions are resolved
+/// authoritatively, and feed the resulting usable spans into a `RangeSet` for
+/// tracking and allocation. We also subtract off the first 1 MiB of memory to
+/// prevent BIOS data structures from being overwritten.
+pub fn init() {
     // Create a new empty `RangeSet` for tracking free physical memory
     let mut free_memory = RangeSet::new();
 
-    // Iterate twice as some BIOSes have used memory ranges inside other memory ranges
-    // Loop through the memory the BIOS reports twice.
-    // The 1st time we accumulate all of the memory that is marked as freee.
-    // The 2nd time we remove all ranges that are not marked as free.
-    // This sanitizes the BIOS memory map, and makes sure that any memory marked
-    // both free and non-free, is not marked free at all.
-    for &add_free_mem in &[true, false] {
-        // Allocate a register state to use when doing the E820 call
-        let mut regs = RegisterState::default();
-
-        // Set the continuation code to 0 for the first E820 call
-        regs.ebx = 0;
-        loop {
-            /// Raw E820 entry, to be filled in by the BIOS
-            #[derive(Debug, Default)]
-            #[repr(C)]
-            struct E820Entry {
-                base: u64,
-                size: u64,
-                typ: u32,
-            }
+    // Collect the raw map from the BIOS
+    let mut entries = [E820Entry::default(); MAX_E820_ENTRIES];
+    let nentries = read_e820(&mut entries);
 
-            // Create a zeroed out E820 entry
-            let mut entry = E820Entry::default();
+    // Build 2 change points per region: one at its start and one at its
+    // (end + 1), the latter held as a `u128` exclusive bound to avoid wrapping.
+    let mut points = [ChangePoint::default(); MAX_E820_ENTRIES * 2];
+    for i in 0..nentries {
+        let base = entries[i].base as u128;
+        let end_excl = base + entries[i].size as u128;
+        points[i * 2] = ChangePoint { addr: base, is_start: true, idx: i };
+        points[i * 2 + 1] = ChangePoint { addr: end_excl, is_start: false, idx: i };
+    }
+    let npoints = nentries * 2;
 
-            // Set up the args for E820, we use the previous continuation code
-            regs.eax = 0xe820;
-            regs.edi = &mut entry as *mut E820Entry as u32;
-            regs.ecx = core::mem::size_of_val(&entry) as u32;
-            regs.edx = u32::from_be_bytes(*b"SMAP");
+    // Sort by address. At a shared address, starts are ordered before matching
+    // ends so a region is considered open across its own span.
+    points[..npoints].sort_unstable_by(|a, b| {
+        a.addr.cmp(&b.addr).then((!a.is_start).cmp(&(!b.is_start)))
+    });
 
-            // Invoke the BIOS for the E820 memory map
-            unsafe { invoke_realmode(0x15, &mut regs); }
+    // Sweep through the change points, maintaining the set of currently
+    // overlapping regions. Between consecutive change points we emit a single
+    // span whose type is the most restrictive of the open regions; only usable
+    // spans are fed to the `RangeSet`, which coalesces contiguous inserts.
+    let mut open = [false; MAX_E820_ENTRIES];
+    let mut overlap = 0usize;
+    let mut idx = 0;
+    let mut typed_map = MemoryMap::new();
+    while idx < npoints {
+        let addr = points[idx].addr;
 
-            // Check the CF for an error
-            if (regs.efl & 1) != 0 {
-                panic!("Error reported by BIOS on E820");
+        // Apply every change point at this address
+        while idx < npoints && points[idx].addr == addr {
+            let p = points[idx];
+            if p.is_start {
+                open[p.idx] = true;
+                overlap += 1;
+            } else {
+                open[p.idx] = false;
+                overlap -= 1;
             }
+            idx += 1;
+        }
 
-            if add_free_mem && entry.typ == 1 && entry.size > 0{
-                // If the entry is free, mark the memory as free
-                free_memory.insert(Range {
-                    start: entry.base,
-                    end: entry.base.checked_add(entry.size - 1).unwrap(),
-                });
-            } else if !add_free_mem && entry.typ != 1 && entry.size > 0 {
-                // If the memory is markes as non-free, remove it from the
-                // range
-                free_memory.remove(Range {
-                    start: entry.base,
-                    end: entry.base.checked_add(entry.size - 1).unwrap(),
-                });
+        // The gap runs from this address to the next change point. If regions
+        // are open across it, emit the most restrictive type.
+        if overlap > 0 && idx < npoints {
+            let next = points[idx].addr;
+
+            let mut winner = E820_USABLE;
+            let mut best = 0u32;
+            for i in 0..nentries {
+                if open[i] && type_priority(entries[i].typ) >= best {
+                    best = type_priority(entries[i].typ);
+                    winner = entries[i].typ;
+                }
             }
 
-            if regs.ebx == 0 {
-                // Last entry
-                break;
+            let range = Range { start: addr as u64, end: (next - 1) as u64 };
+
+            // Record the region in the full typed map for kernel handoff
+            typed_map.push(range, E820Type::from_raw(winner));
+
+            if winner == E820_USABLE {
+                free_memory.insert(range);
             }
         }
     }
@@ -129,7 +435,113 @@ pub fn init() {
         end: (1024 * 1024) - 1,
     });
 
-    // Set up the global physical memory state with the free memory we have
-    // tracked.
-    *pmem = Some(free_memory);
+    // Retain the full typed map, then install the free memory as the BIOS
+    // allocation backend.
+    *MEMORY_MAP.lock() = Some(typed_map);
+    GLOBAL_ALLOCATOR.init_bios(free_memory);
+}
+
+/// A UEFI memory descriptor as returned by `GetMemoryMap`
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct EfiMemoryDescriptor {
+    typ: u32,
+    _pad: u32,
+    physical_start: u64,
+    virtual_start: u64,
+    number_of_pages: u64,
+    attribute: u64,
+}
+
+// UEFI memory types that become usable RAM for us. `EfiConventionalMemory` is
+// free outright, and the loader/boot-services regions are reclaimable once boot
+// services have been exited.
+const EFI_LOADER_CODE: u32 = 1;
+const EFI_BOOT_SERVICES_CODE: u32 = 3;
+const EFI_BOOT_SERVICES_DATA: u32 = 4;
+const EFI_CONVENTIONAL_MEMORY: u32 = 7;
+// (`EFI_LOADER_DATA` is defined above for our own page allocations.)
+
+// Non-usable UEFI memory types we classify for the retained typed map
+const EFI_UNUSABLE_MEMORY: u32 = 8;
+const EFI_ACPI_RECLAIM_MEMORY: u32 = 9;
+const EFI_ACPI_MEMORY_NVS: u32 = 10;
+
+/// Map a UEFI memory type onto the same `E820Type` classification used by the
+/// BIOS path, so the retained typed map is firmware-agnostic. The reclaimable
+/// loader/boot-services regions become `Usable` to match the allocator's view.
+fn efi_type(typ: u32) -> E820Type {
+    match typ {
+        EFI_LOADER_CODE | EFI_LOADER_DATA | EFI_BOOT_SERVICES_CODE |
+        EFI_BOOT_SERVICES_DATA | EFI_CONVENTIONAL_MEMORY => E820Type::Usable,
+        EFI_ACPI_RECLAIM_MEMORY => E820Type::AcpiReclaimable,
+        EFI_ACPI_MEMORY_NVS => E820Type::AcpiNvs,
+        EFI_UNUSABLE_MEMORY => E820Type::BadMemory,
+        _ => E820Type::Reserved,
+    }
+}
+
+/// Initialize the physical memory manager from the UEFI Boot Services
+/// `GetMemoryMap` descriptor array rather than from E820. `memory_map` points
+/// at the first descriptor, `map_size` is the array size in bytes and
+/// `descriptor_size` is the firmware-reported stride between descriptors (which
+/// may exceed `size_of::<EfiMemoryDescriptor>()`). The resulting internal state
+/// is identical to the E820 path so the rest of the allocator is
+/// firmware-agnostic.
+///
+/// # Safety
+///
+/// `memory_map`/`map_size`/`descriptor_size` must describe a valid UEFI memory
+/// map obtained from `GetMemoryMap`.
+pub unsafe fn init_uefi(memory_map: *const u8, map_size: usize,
+        descriptor_size: usize) {
+    // Create a new empty `RangeSet` for tracking free physical memory
+    let mut free_memory = RangeSet::new();
+
+    // Walk the descriptor array using the firmware-reported stride, building
+    // both the free `RangeSet` and the full typed map for kernel handoff.
+    let mut typed_map = MemoryMap::new();
+    let mut offset = 0;
+    while offset + descriptor_size <= map_size {
+        let desc = &*(memory_map.add(offset) as *const EfiMemoryDescriptor);
+
+        if desc.number_of_pages > 0 {
+            let size = desc.number_of_pages * EFI_PAGE_SIZE as u64;
+            let range = Range {
+                start: desc.physical_start,
+                end: desc.physical_start.checked_add(size - 1).unwrap(),
+            };
+
+            // Record the region in the full typed map for kernel handoff
+            let typ = efi_type(desc.typ);
+            typed_map.push(range, typ);
+
+            if typ == E820Type::Usable {
+                free_memory.insert(range);
+            }
+        }
+
+        offset += descriptor_size;
+    }
+
+    // Descriptors arrive in firmware order; sort the typed map by address and
+    // re-coalesce same-typed contiguous neighbors
+    let n = typed_map.len;
+    typed_map.entries[..n].sort_unstable_by_key(|&(range, _)| range.start);
+    typed_map.len = 0;
+    for i in 0..n {
+        let (range, typ) = typed_map.entries[i];
+        typed_map.push(range, typ);
+    }
+
+    // Remove the first 1 MB of memory for use, matching the E820 path.
+    free_memory.remove(Range {
+        start: 0x0,
+        end: (1024 * 1024) - 1,
+    });
+
+    // Retain the full typed map and converge on the same BIOS-style backend so
+    // `memory_map()` works regardless of which firmware path initialized us.
+    *MEMORY_MAP.lock() = Some(typed_map);
+    GLOBAL_ALLOCATOR.init_bios(free_memory);
 }
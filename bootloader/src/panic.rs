@@ -16,5 +16,55 @@ fn panic(info: &PanicInfo) -> ! {
 
     print!("\n");
 
+    // Walk the frame-pointer chain and dump the return addresses. These are
+    // raw addresses meant to be symbolized offline (addr2line/gimli on the
+    // host), turning the panic into an actionable trace.
+    backtrace();
+
     cpu::halt();
 }
+
+/// Maximum number of frames to walk before giving up on a (possibly corrupt)
+/// stack
+const MAX_FRAMES: usize = 64;
+
+/// Print a frame-pointer-based stack backtrace. Each frame stores the caller's
+/// `rbp` at `[rbp]` and the return address at `[rbp + 8]`. The walk stops at a
+/// null or misaligned frame pointer, a zero return address, a saved `rbp` that
+/// does not increase, or after `MAX_FRAMES` frames. Frame pointers are not
+/// checked against the bounds of the stack, so a corrupt chain that points at
+/// unmapped memory can still fault.
+fn backtrace() {
+    print!("Backtrace:\n");
+
+    let mut rbp = cpu::read_rbp();
+
+    for frame in 0..MAX_FRAMES {
+        // A null or misaligned frame pointer means we've reached the end of the
+        // chain (or it is corrupt); either way, stop.
+        if rbp == 0 || (rbp & 7) != 0 {
+            break;
+        }
+
+        // Read the saved caller `rbp` and the return address for this frame
+        let next_rbp = unsafe { core::ptr::read_volatile(rbp as *const usize) };
+        let ret_addr = match rbp.checked_add(8) {
+            Some(addr) => unsafe { core::ptr::read_volatile(addr as *const usize) },
+            None => break,
+        };
+
+        // A zeroed return address marks the outermost frame
+        if ret_addr == 0 {
+            break;
+        }
+
+        print!("#{} {:#018x}\n", frame, ret_addr);
+
+        // Frames grow towards higher addresses; a non-increasing saved `rbp`
+        // means the chain is corrupt, so bail out rather than loop forever.
+        if next_rbp <= rbp {
+            break;
+        }
+        rbp = next_rbp;
+    }
+}
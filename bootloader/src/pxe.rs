@@ -6,7 +6,24 @@ fn segoff_to_linear(seg: u16, off: u16) -> usize {
     ((seg as usize) << 4) + off as usize
 }
 
-pub fn download<P: AsRef<[u8]>>(filename: P) -> Option<()> {
+/// Split a 16-byte-aligned linear address below 1 MiB into a `seg:0` pointer the
+/// 16-bit PXE API can reach. Unlike stuffing a full pointer into `buffer_off`
+/// with `buffer_seg = 0` (which truncates to the first 64 KiB), the segment
+/// carries the high bits so any address in the first MiB is addressable.
+fn linear_to_segoff(addr: usize) -> (u16, u16) {
+    ((addr >> 4) as u16, 0)
+}
+
+/// Fixed scratch buffer in low conventional memory (below 1 MiB) used as the
+/// DMA target for each TFTP block, so the NIC writes somewhere the 16-bit API
+/// can address. The bytes are copied out into the caller's buffer after each
+/// read.
+const TFTP_BOUNCE_ADDR: usize = 0x7_0000;
+
+/// Download `filename` from the PXE TFTP server into `buffer`, returning the
+/// number of bytes received. Returns `None` if the PXE API is missing or any
+/// step of the transfer fails.
+pub fn download<P: AsRef<[u8]>>(filename: P, buffer: &mut [u8]) -> Option<usize> {
     // Convert the filename to a slice of bytes
     let filename = filename.as_ref();
 
@@ -102,16 +119,24 @@ pub fn download<P: AsRef<[u8]>>(filename: P) -> Option<()> {
 
         // Extract the serve IP
         pkt_buf[0x14..0x18].try_into().ok()?
-
-    }
+    };
 
     serial::print!("Server IP: {}.{}.{}.{}\n",
                 server_ip[0], server_ip[1], server_ip[2], server_ip[3]);
+
+    // The UDP port the TFTP server listens on and the block size we ask for.
+    // The port is passed to the API in network byte order.
+    const TFTP_PORT:   u16 = 69;
+    const PACKET_SIZE: u16 = 512;
+
     // Get the file size for the next stage
-    {
-        const PXE_TFTP_GET_FILE_SIZE: u16 = 0x71;
+    let file_size = {
+        const PXENV_TFTP_GET_FILE_SIZE: u16 = 0x25;
 
-        #[derive(Default)]
+        // The BIOS writes `file_size` immediately after the 128-byte filename,
+        // at offset 138, so the struct must be packed: with natural alignment
+        // the `u32` would slip to offset 140 and we'd read garbage.
+        #[repr(C, packed)]
         struct GetFileSize {
             status: u16,
             server_ip: [u8; 4],
@@ -120,9 +145,15 @@ pub fn download<P: AsRef<[u8]>>(filename: P) -> Option<()> {
             file_size: u32,
         }
 
-        let mut st = GetFileSize::default();
-        st.server_ip = server_ip;
-        st.gateway_ip = [0; 4];
+        // `[u8; 128]` has no `Default`, so every field below (and in the
+        // similarly-shaped `TftpOpen` request further down) is filled by hand
+        let mut st = GetFileSize {
+            status: 0,
+            server_ip,
+            gateway_ip: [0; 4],
+            filename: [0u8; 128],
+            file_size: 0,
+        };
 
         // Check to see if we have enough room for the filename and null
         // terminator
@@ -130,14 +161,146 @@ pub fn download<P: AsRef<[u8]>>(filename: P) -> Option<()> {
             return None;
         }
 
-        // Copy in the filename
-        st.filename.copy_from_slice(filename);
+        // Copy in the filename, leaving the trailing zero as the terminator
+        st.filename[..filename.len()].copy_from_slice(filename);
+
+        unsafe {
+            pxecall(ep_seg, ep_off, PXENV_TFTP_GET_FILE_SIZE,
+                0, &mut st as *mut _ as u16);
+        }
+
+        // Make sure the call succeeded
+        if st.status != 0 {
+            return None;
+        }
+
+        // Read the packed field through a copy to avoid an unaligned reference
+        let file_size = st.file_size;
+        file_size as usize
+    };
+
+    // Open the file for reading. The TFTP session stays open until we issue
+    // the matching close below.
+    {
+        const PXENV_TFTP_OPEN: u16 = 0x20;
+
+        // All-`u16` after the arrays, so natural `#[repr(C)]` layout is correct
+        #[repr(C)]
+        struct TftpOpen {
+            status: u16,
+            server_ip: [u8; 4],
+            gateway_ip: [u8; 4],
+            filename: [u8; 128],
+            tftp_port: u16,
+            packet_size: u16,
+        }
+
+        let mut st = TftpOpen {
+            status: 0,
+            server_ip,
+            gateway_ip: [0; 4],
+            filename: [0u8; 128],
+            tftp_port: TFTP_PORT.to_be(),
+            packet_size: PACKET_SIZE,
+        };
+
+        if filename.len() + 1 > st.filename.len() {
+            return None;
+        }
+        st.filename[..filename.len()].copy_from_slice(filename);
+
+        unsafe {
+            pxecall(ep_seg, ep_off, PXENV_TFTP_OPEN,
+                0, &mut st as *mut _ as u16);
+        }
+
+        if st.status != 0 {
+            return None;
+        }
+    }
+
+    // Read the file one block at a time. A block shorter than the negotiated
+    // packet size signals the end of the transfer. Each block is DMA'd into a
+    // fixed low-memory bounce buffer (addressable by the 16-bit API via a real
+    // seg:off) and then copied into the caller's buffer.
+    let received = {
+        const PXENV_TFTP_READ: u16 = 0x22;
+
+        #[derive(Default)]
+        #[repr(C)]
+        struct TftpRead {
+            status: u16,
+            packet_number: u16,
+            buffer_size: u16,
+            buffer_off: u16,
+            buffer_seg: u16,
+        }
+
+        // The low-memory packet buffer the NIC writes each block into
+        let (bounce_seg, bounce_off) = linear_to_segoff(TFTP_BOUNCE_ADDR);
+
+        let mut received = 0usize;
+        loop {
+            let mut st = TftpRead::default();
+            st.buffer_size = PACKET_SIZE;
+            st.buffer_seg = bounce_seg;
+            st.buffer_off = bounce_off;
+
+            unsafe {
+                pxecall(ep_seg, ep_off, PXENV_TFTP_READ,
+                    0, &mut st as *mut _ as u16);
+            }
+
+            if st.status != 0 {
+                return None;
+            }
+
+            // Copy the returned block out of the bounce buffer. A block can
+            // never exceed the packet size we negotiated.
+            let got = core::cmp::min(st.buffer_size as usize,
+                PACKET_SIZE as usize);
+            let block = unsafe {
+                core::slice::from_raw_parts(TFTP_BOUNCE_ADDR as *const u8, got)
+            };
+            buffer.get_mut(received..received.checked_add(got)?)?
+                .copy_from_slice(block);
+            received += got;
+
+            // A short block marks the end of the file
+            if got < PACKET_SIZE as usize {
+                break;
+            }
+        }
+
+        received
+    };
+
+    // Close the TFTP session
+    {
+        const PXENV_TFTP_CLOSE: u16 = 0x21;
+
+        #[derive(Default)]
+        #[repr(C)]
+        struct TftpClose {
+            status: u16,
+        }
+
+        let mut st = TftpClose::default();
 
         unsafe {
-            pxecall(ep_seg, 
+            pxecall(ep_seg, ep_off, PXENV_TFTP_CLOSE,
+                0, &mut st as *mut _ as u16);
         }
 
+        if st.status != 0 {
+            return None;
+        }
+    }
+
+    // The bytes we read must match what the server reported earlier
+    if received != file_size {
+        return None;
     }
 
-    Some(())
+    Some(received)
 }
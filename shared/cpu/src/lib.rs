@@ -23,6 +23,40 @@ pub unsafe fn in8(addr: u16) -> u8{
     val
 }
 
+/// Load the global descriptor table from the table pointer at `addr`
+#[inline]
+pub unsafe fn lgdt(addr: usize) {
+    llvm_asm!("lgdt [$0]" :: "r"(addr) :: "volatile", "intel");
+}
+
+/// Load the interrupt descriptor table from the table pointer at `addr`
+#[inline]
+pub unsafe fn lidt(addr: usize) {
+    llvm_asm!("lidt [$0]" :: "r"(addr) :: "volatile", "intel");
+}
+
+/// Enable interrupts
+#[inline]
+pub unsafe fn sti() {
+    llvm_asm!("sti" :::: "volatile", "intel");
+}
+
+/// Disable interrupts
+#[inline]
+pub unsafe fn cli() {
+    llvm_asm!("cli" :::: "volatile", "intel");
+}
+
+/// Read the current frame pointer (`rbp`)
+#[inline(always)]
+pub fn read_rbp() -> usize {
+    let rbp: usize;
+    unsafe {
+        llvm_asm!("mov $0, rbp" : "=r"(rbp) ::: "intel");
+    }
+    rbp
+}
+
 /// Disable interrupts and halt forever
 #[inline]
 pub fn halt() -> ! {
@@ -88,11 +88,135 @@ impl<'a, T: ?Sized> DerefMut for LockCellGuard<'a, T> {
     }
 }
 
+/// A fair reader/writer spinlock. Readers can share access concurrently while
+/// writers get exclusive access. Ordering is preserved with the same
+/// `ticket`/`release` pair as [`LockCell`], extended with a live reader count.
+pub struct RwLockCell<T: ?Sized> {
+    /// Ticket counter to get new tickets to access the `val`
+    ticket: AtomicUsize,
+
+    /// Current ticket value which can be released
+    release: AtomicUsize,
+
+    /// Number of readers currently holding shared access
+    readers: AtomicUsize,
+
+    /// Value which is guarded by locks
+    val: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized> Sync for RwLockCell<T> {}
+
+impl<T> RwLockCell<T> {
+    /// Move a `val` into a `RwLockCell`, a type which allows shared read access
+    /// or exclusive write access around ticket spinlocks.
+    pub const fn new(val: T) -> Self {
+        RwLockCell {
+            val: UnsafeCell::new(val),
+            ticket: AtomicUsize::new(0),
+            release: AtomicUsize::new(0),
+            readers: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T: ?Sized> RwLockCell<T> {
+    /// Acquire shared read access to `self`. Multiple readers can hold the lock
+    /// at once; a pending writer waits until they all drop.
+    pub fn read(&self) -> RwReadGuard<T> {
+        // Get a ticket and wait for our turn
+        let ticket = self.ticket.fetch_add(1, Ordering::SeqCst);
+        while self.release.load(Ordering::SeqCst) != ticket {
+            spin_loop();
+        }
+
+        // Register ourselves as a reader, then immediately hand the ticket on
+        // so other readers queued behind us can proceed concurrently
+        self.readers.fetch_add(1, Ordering::SeqCst);
+        self.release.fetch_add(1, Ordering::SeqCst);
+
+        RwReadGuard {
+            cell: self,
+        }
+    }
+
+    /// Acquire exclusive write access to `self`, waiting for our ticket and for
+    /// every outstanding reader to drop.
+    pub fn write(&self) -> RwWriteGuard<T> {
+        // Get a ticket
+        let ticket = self.ticket.fetch_add(1, Ordering::SeqCst);
+
+        // Spin until our ticket is up and no readers are still active
+        while self.release.load(Ordering::SeqCst) != ticket ||
+                self.readers.load(Ordering::SeqCst) != 0 {
+            spin_loop();
+        }
+
+        RwWriteGuard {
+            cell: self,
+        }
+    }
+}
+
+/// A guard granting shared read access, decrementing the reader count on drop.
+pub struct RwReadGuard<'a, T: ?Sized> {
+    /// A reference to the value we currently have shared access to
+    cell: &'a RwLockCell<T>,
+}
+
+impl<'a, T: ?Sized> Drop for RwReadGuard<'a, T> {
+    fn drop(&mut self) {
+        // Drop our reader reference, potentially unblocking a waiting writer
+        self.cell.readers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            &*self.cell.val.get()
+        }
+    }
+}
+
+/// A guard granting exclusive write access, releasing the ticket on drop.
+pub struct RwWriteGuard<'a, T: ?Sized> {
+    /// A reference to the value we currently have exclusive access to
+    cell: &'a RwLockCell<T>,
+}
+
+impl<'a, T: ?Sized> Drop for RwWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        // Release the lock
+        self.cell.release.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            &*self.cell.val.get()
+        }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe {
+            &mut *self.cell.val.get()
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     extern crate std;
 
-    use crate::LockCell;
+    use crate::{LockCell, RwLockCell};
 
     #[test]
     fn test_lock() {
@@ -125,4 +249,28 @@ mod test {
         std::mem::drop(_lk);
         std::mem::drop(_var);
     }
+
+    #[test]
+    fn test_rwlock() {
+        static VAR: RwLockCell<usize> = RwLockCell::new(5);
+
+        {
+            // A writer gets exclusive, mutable access
+            let mut w = VAR.write();
+            assert!(*w == 5);
+            *w = 10;
+        }
+        {
+            // Multiple readers can be live at the same time
+            let r1 = VAR.read();
+            let r2 = VAR.read();
+            assert!(*r1 == 10 && *r2 == 10);
+        }
+        {
+            // Access is serialized again once the readers drop
+            let mut w = VAR.write();
+            *w += 1;
+            assert!(*w == 11);
+        }
+    }
 }
@@ -6,6 +6,11 @@ use core::convert::TryInto;
 const IMAGE_FILE_MACHINE_I386: u16 = 0x014c;
 const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
 
+// Base relocation types stored in the top 4 bits of each `.reloc` entry
+const IMAGE_REL_BASED_ABSOLUTE: u16 = 0;
+const IMAGE_REL_BASED_HIGHLOW:  u16 = 3;
+const IMAGE_REL_BASED_DIR64:    u16 = 10;
+
 /// A validated PE file that has had some basic information parsed out of it.
 /// You can use functions on this structure to extract things like sections.
 pub struct PeParser<'a> {
@@ -21,6 +26,13 @@ pub struct PeParser<'a> {
     /// Base of the image
     image_base: u64,
 
+    /// Offset into the raw PE file where the optional header starts
+    opt_header_off: usize,
+
+    /// Machine type (`IMAGE_FILE_MACHINE_I386`/`_AMD64`), used to locate the
+    /// data directory and pick the relocation width
+    machine: u16,
+
     /// Virtual address of the entry point(includes image base)
     pub entry_point: u64,
 }
@@ -99,12 +111,106 @@ impl<'a> PeParser<'a> {
         Some(PeParser {
             bytes,
             image_base,
+            machine,
             nsections,
             entry_point,
+            opt_header_off: pe_offset + 0x18,
             section_off: pe_offset + 0x18 + opt_header_size,
         })
     }
 
+    /// Flatten the PE image into `dest` as if loaded at `actual_base` and apply
+    /// the base relocations so all absolute references point at `actual_base`
+    /// rather than the file's preferred `image_base`. `dest` is indexed by
+    /// virtual address relative to the image base, and every access is bounds
+    /// checked against it. Returns `None` if the image does not fit in `dest`
+    /// or a relocation is malformed.
+    pub fn load_into(&self, dest: &mut [u8], actual_base: u64) -> Option<()> {
+        // Flatten each section into its virtual-address-relative slot, zeroing
+        // the slack so uninitialized (.bss) bytes start cleared
+        self.sections(|vaddr, vsize, raw| {
+            let offset = (vaddr.checked_sub(self.image_base)?) as usize;
+            let slot = dest.get_mut(offset..offset.checked_add(vsize as usize)?)?;
+
+            for byte in slot.iter_mut() { *byte = 0; }
+            let n = core::cmp::min(raw.len(), vsize as usize);
+            slot[..n].copy_from_slice(&raw[..n]);
+
+            Some(())
+        })?;
+
+        // Nothing to fix up if the image is loaded at its preferred base
+        let delta = actual_base.wrapping_sub(self.image_base);
+        if delta == 0 {
+            return Some(());
+        }
+
+        // Locate the base relocation data directory (entry 5). The DataDirectory
+        // array starts at a fixed offset from the optional header (0x60 for PE32,
+        // 0x70 for PE32+); each entry is 8 bytes, so BASERELOC is `5 * 8` in.
+        let data_dir_off = self.opt_header_off + if self.machine == IMAGE_FILE_MACHINE_I386 {
+            0x60
+        } else {
+            0x70
+        };
+        let dir_off = data_dir_off + 5 * 8;
+        let reloc_rva = u32::from_le_bytes(
+            self.bytes.get(dir_off..dir_off + 4)?.try_into().ok()?) as usize;
+        let reloc_size = u32::from_le_bytes(
+            self.bytes.get(dir_off + 4..dir_off + 8)?.try_into().ok()?) as usize;
+
+        // Walk the `.reloc` blocks, which live in the flattened image
+        let mut off = reloc_rva;
+        let end = reloc_rva.checked_add(reloc_size)?;
+        while off < end {
+            // Each block is an 8-byte header: page RVA then block size
+            let page_rva = u32::from_le_bytes(
+                dest.get(off..off + 4)?.try_into().ok()?) as usize;
+            let block_size = u32::from_le_bytes(
+                dest.get(off + 4..off + 8)?.try_into().ok()?) as usize;
+
+            // A degenerate block would loop forever; treat it as malformed
+            if block_size < 8 {
+                return None;
+            }
+
+            // The remaining bytes are 16-bit entries
+            let entries = (block_size - 8) / 2;
+            for i in 0..entries {
+                let eoff = off + 8 + i * 2;
+                let entry = u16::from_le_bytes(
+                    dest.get(eoff..eoff + 2)?.try_into().ok()?);
+
+                // Top 4 bits are the type, low 12 bits the offset in the page
+                let typ = entry >> 12;
+                let target = page_rva.checked_add((entry & 0xfff) as usize)?;
+
+                match typ {
+                    IMAGE_REL_BASED_ABSOLUTE => {
+                        // Padding entry, nothing to do
+                    }
+                    IMAGE_REL_BASED_HIGHLOW => {
+                        let slot = dest.get_mut(target..target.checked_add(4)?)?;
+                        let val = u32::from_le_bytes(slot.try_into().ok()?)
+                            .wrapping_add(delta as u32);
+                        slot.copy_from_slice(&val.to_le_bytes());
+                    }
+                    IMAGE_REL_BASED_DIR64 => {
+                        let slot = dest.get_mut(target..target.checked_add(8)?)?;
+                        let val = u64::from_le_bytes(slot.try_into().ok()?)
+                            .wrapping_add(delta);
+                        slot.copy_from_slice(&val.to_le_bytes());
+                    }
+                    _ => return None,
+                }
+            }
+
+            off += block_size;
+        }
+
+        Some(())
+    }
+
     /// Invoke a closure with the format
     /// (virtual add, virtual size, raw initialize bytes) for each section
     /// in the PE file
@@ -147,9 +253,61 @@ mod tests {
     extern crate std;
 
     use crate::*;
+    use std::vec;
+
+    /// Copy the bytes of `val` into `buf` at `off`
+    fn put(buf: &mut [u8], off: usize, val: &[u8]) {
+        buf[off..off + val.len()].copy_from_slice(val);
+    }
 
+    /// Hand-assemble a minimal PE32+ image with a single section that holds both
+    /// the relocation targets and the `.reloc` blocks, then load it at a shifted
+    /// base and confirm the HIGHLOW and DIR64 fixups got the delta applied.
     #[test]
-    fn it_works() {
-        flatten_pe();
+    fn load_into_applies_fixups() {
+        const PE_OFF: usize = 0x80;
+        const OPT_SIZE: usize = 0xf0;
+        const IMAGE_BASE: u64 = 0x1_4000_0000;
+        const DELTA: u64 = 0x1_0000;
+
+        let mut file = vec![0u8; 0x400];
+        put(&mut file, 0, b"MZ");
+        put(&mut file, 0x3c, &(PE_OFF as u32).to_le_bytes());
+        put(&mut file, PE_OFF, b"PE\0\0");
+        put(&mut file, PE_OFF + 4, &IMAGE_FILE_MACHINE_AMD64.to_le_bytes());
+        put(&mut file, PE_OFF + 6, &1u16.to_le_bytes());       // nsections
+        put(&mut file, PE_OFF + 0x14, &(OPT_SIZE as u16).to_le_bytes());
+        put(&mut file, PE_OFF + 0x28, &0x1000u32.to_le_bytes()); // entry rva
+        put(&mut file, PE_OFF + 0x30, &IMAGE_BASE.to_le_bytes());
+
+        // DataDirectory entry 5 (BASERELOC): opt_header + 0x70 + 5*8
+        let dir_off = PE_OFF + 0x18 + 0x70 + 5 * 8;
+        put(&mut file, dir_off, &0x1100u32.to_le_bytes());     // reloc rva
+        put(&mut file, dir_off + 4, &12u32.to_le_bytes());     // reloc size
+
+        // Single section header, raw data at file offset 0x200 mapping to rva 0x1000
+        let sec = PE_OFF + 0x18 + OPT_SIZE;
+        put(&mut file, sec, b".reloc\0\0");
+        put(&mut file, sec + 0x8, &0x200u32.to_le_bytes());    // virt size
+        put(&mut file, sec + 0xc, &0x1000u32.to_le_bytes());   // virt addr
+        put(&mut file, sec + 0x10, &0x200u32.to_le_bytes());   // raw size
+        put(&mut file, sec + 0x14, &0x200u32.to_le_bytes());   // raw off
+
+        // Section contents: a DIR64 pointer, a HIGHLOW value, then one reloc block
+        put(&mut file, 0x200, &(IMAGE_BASE + 0x1000).to_le_bytes()); // rva 0x1000
+        put(&mut file, 0x208, &0x1008u32.to_le_bytes());             // rva 0x1008
+        put(&mut file, 0x300, &0x1000u32.to_le_bytes());             // page rva
+        put(&mut file, 0x304, &12u32.to_le_bytes());                 // block size
+        put(&mut file, 0x308, &((IMAGE_REL_BASED_DIR64 << 12) | 0).to_le_bytes());
+        put(&mut file, 0x30a, &((IMAGE_REL_BASED_HIGHLOW << 12) | 8).to_le_bytes());
+
+        let pe = PeParser::parse(&file).expect("valid PE");
+        let mut dest = vec![0u8; 0x1200];
+        pe.load_into(&mut dest, IMAGE_BASE + DELTA).expect("load_into");
+
+        let dir64 = u64::from_le_bytes(dest[0x1000..0x1008].try_into().unwrap());
+        assert_eq!(dir64, IMAGE_BASE + 0x1000 + DELTA);
+        let highlow = u32::from_le_bytes(dest[0x1008..0x100c].try_into().unwrap());
+        assert_eq!(highlow, 0x1008 + DELTA as u32);
     }
 }